@@ -38,11 +38,20 @@ use std::iter::repeat_with;
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
 
-struct Seed(*const ());
+/// Seedable pseudo-random word generator driving the witness selection.
+///
+/// It advances a [SplitMix64](https://prng.di.unimi.it/splitmix64.c) state so
+/// that identical seeds always yield identical base sequences, which makes
+/// witness selection reproducible and independently seedable.
+struct Seed(u64);
 impl ThreadRandGen for Seed {
     fn gen(&mut self) -> u32 {
-        // not really random
-        rand::random()
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 32) as u32
     }
 }
 
@@ -66,6 +75,154 @@ fn decompose(n: &Integer) -> (Integer, Integer) {
     (d, r)
 }
 
+/// Pick the minimal deterministic witness set for an `n` that fits in 64 bits.
+///
+/// For every threshold the listed bases are known to give a provably correct
+/// answer, so running all of them (no early randomness) decides primality
+/// exactly. The 12-base set covering the whole `u64` range is the fallback.
+fn deterministic_witnesses(n: &Integer) -> Vec<u8> {
+    if n < &Integer::from(2_047u64) {
+        vec![2]
+    } else if n < &Integer::from(1_373_653u64) {
+        vec![2, 3]
+    } else if n < &Integer::from(9_080_191u64) {
+        vec![31, 73]
+    } else if n < &Integer::from(25_326_001u64) {
+        vec![2, 3, 5]
+    } else if n < &Integer::from(3_215_031_751u64) {
+        vec![2, 3, 5, 7]
+    } else if n < &Integer::from(3_474_749_660_383u64) {
+        vec![2, 3, 5, 7, 11, 13]
+    } else if n < &Integer::from(341_550_071_728_321u64) {
+        vec![2, 3, 5, 7, 11, 13, 17]
+    } else {
+        vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
+    }
+}
+
+/// Montgomery arithmetic over an odd modulus `n` that fits in 64 bits.
+///
+/// Keeping every operand in the Montgomery domain lets the inner Miller-Rabin
+/// loop run on native `u64`/`u128` arithmetic instead of GMP's generic
+/// `pow_mod`, which is what makes the sub-`2^64` path fast.
+struct Mont {
+    /// The (odd) modulus.
+    n: u64,
+    /// `n^{-1} mod 2^64`, negated so that `m = t.wrapping_mul(ni)` is the
+    /// Montgomery reduction constant (`n.wrapping_mul(ninv) == 1` for the
+    /// un-negated inverse computed below).
+    ni: u64,
+    /// `2^64 mod n`, i.e. the Montgomery form of `1`.
+    r: u64,
+    /// `2^128 mod n`, used to lift a value into the Montgomery domain.
+    r2: u64,
+    /// Odd part `d` of `n - 1 = 2^k · d`.
+    d: u64,
+    /// Power of two `k` of `n - 1 = 2^k · d`.
+    k: u32,
+}
+
+impl Mont {
+    /// Build the Montgomery context for an odd modulus `n`.
+    fn new(n: u64) -> Self {
+        // Newton iteration for the inverse of `n` modulo `2^64`: starting from
+        // `ninv = n`, five rounds are enough for the full 64-bit width.
+        let mut ninv = n;
+        for _ in 0..5 {
+            ninv = ninv.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(ninv)));
+        }
+
+        let r = ((1u128 << 64) % n as u128) as u64;
+        let r2 = ((r as u128 * r as u128) % n as u128) as u64;
+
+        let mut d = n - 1;
+        let mut k = 0u32;
+        while d & 1 == 0 {
+            d >>= 1;
+            k += 1;
+        }
+
+        Mont {
+            n,
+            ni: ninv.wrapping_neg(),
+            r,
+            r2,
+            d,
+            k,
+        }
+    }
+
+    /// Montgomery product: `a · b · 2^{-64} mod n`.
+    ///
+    /// `t + m·n` can reach ~`2·n·2^64`, which overflows `u128` once `n` is
+    /// close to `u64::MAX` — so the addition is split into high/low halves
+    /// instead of being formed directly. By construction the low 64 bits of
+    /// `t` and `m·n` always cancel (`m` is chosen so `t + m·n ≡ 0 mod 2^64`),
+    /// so only the carry out of that cancellation ever needs to be folded
+    /// into the high halves, and the three high-half terms summed below are
+    /// each `< n`, leaving plenty of headroom in `u128`.
+    fn mrmul(&self, a: u64, b: u64) -> u64 {
+        let t = (a as u128) * (b as u128);
+        let t_lo = t as u64;
+        let t_hi = (t >> 64) as u64;
+        let m = t_lo.wrapping_mul(self.ni);
+        let mn = (m as u128) * (self.n as u128);
+        let mn_hi = (mn >> 64) as u64;
+        let carry = if t_lo == 0 { 0u128 } else { 1u128 };
+        let u = t_hi as u128 + mn_hi as u128 + carry;
+        if u >= self.n as u128 {
+            (u - self.n as u128) as u64
+        } else {
+            u as u64
+        }
+    }
+
+    /// Lift `a` into the Montgomery domain.
+    fn to_mont(&self, a: u64) -> u64 {
+        self.mrmul(a, self.r2)
+    }
+
+    /// Modular exponentiation `base^exp` with `base` already in the domain.
+    fn pow(&self, mut base: u64, mut exp: u64) -> u64 {
+        let mut acc = self.r; // Montgomery form of 1
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = self.mrmul(acc, base);
+            }
+            base = self.mrmul(base, base);
+            exp >>= 1;
+        }
+        acc
+    }
+
+    /// Strong probable-prime test to base `a`.
+    ///
+    /// Returns `true` when `n` passes (is probably prime for this base) and
+    /// `false` when `a` is a witness to the compositeness of `n`.
+    fn is_strong_probable_prime(&self, a: u64) -> bool {
+        let a = a % self.n;
+        if a == 0 {
+            return true;
+        }
+
+        let minus_one = self.n - self.r; // Montgomery form of n - 1
+        let mut x = self.pow(self.to_mont(a), self.d);
+
+        if x == self.r || x == minus_one {
+            return true;
+        }
+
+        for _ in 1..self.k {
+            x = self.mrmul(x, x);
+            if x == minus_one {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
 /// Running one iteration of Miller-Rabin Test
 fn miller_rabin_iteration(a: &Integer, n: &Integer, d: &Integer, r: &Integer) -> bool {
     let n_minus_one = Integer::from(n - 1u8);
@@ -105,8 +262,25 @@ fn miller_rabin_iteration(a: &Integer, n: &Integer, d: &Integer, r: &Integer) ->
 /// assert!(is_prime(&n, 16));
 /// ```
 pub fn is_prime(n: &Integer, k: usize) -> bool {
+    is_prime_seeded(n, k, rand::random())
+}
+
+/// Reproducible variant of [is_prime] with an explicit `seed` for the random
+/// witness selection.
+///
+/// Identical `(n, k, seed)` inputs always pick identical random bases, so the
+/// test is deterministic and reproducible. Below [u64::MAX] the answer is
+/// decided by the deterministic witness tables and the `seed` has no effect.
+///
+/// # Example
+/// ```
+/// use miller_rabin::is_prime_seeded;
+///
+/// let n = rug::Integer::from(0x7FFF_FFFFu64);
+/// assert_eq!(is_prime_seeded(&n, 16, 42), is_prime_seeded(&n, 16, 42));
+/// ```
+pub fn is_prime_seeded(n: &Integer, k: usize, seed: u64) -> bool {
     let n_minus_one = Integer::from(n - 1u8);
-    let (ref d, ref r) = decompose(n);
 
     if n <= Integer::ONE {
         return false;
@@ -115,31 +289,45 @@ pub fn is_prime(n: &Integer, k: usize) -> bool {
         return true;
     }
     if n <= &Integer::from(0xffff_ffff_ffff_ffffu64) {
-        let samples: Vec<u8> = vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+        let nn = n.to_u64().expect("n fits in u64 in this branch");
+
+        // Even numbers greater than 2 are trivially composite (and Montgomery
+        // arithmetic requires an odd modulus).
+        if nn & 1 == 0 {
+            return false;
+        }
+
+        let mont = Mont::new(nn);
+        let samples: Vec<u8> = deterministic_witnesses(n);
 
         #[cfg(feature = "rayon")]
         return samples
             .par_iter()
             .filter(|&&m| m < n_minus_one)
-            .find_any(|&&a| miller_rabin_iteration(&Integer::from(a), n, d, r))
+            .find_any(|&&a| !mont.is_strong_probable_prime(a as u64))
             .is_none();
 
         #[cfg(not(feature = "rayon"))]
         return samples
             .iter()
             .filter(|&&m| m < n_minus_one)
-            .find(|&&a| miller_rabin_iteration(&Integer::from(a), n, d, r))
+            .find(|&&a| !mont.is_strong_probable_prime(a as u64))
             .is_none();
     }
 
-    let samples: Vec<Integer> = repeat_with(|| {
-        let mut seed = Seed(&());
-        let mut rand = ThreadRandState::new_custom(&mut seed);
-        n_minus_one.clone().random_below(&mut rand)
-    })
-    .filter(|m| m < &n_minus_one)
-    .take(k)
-    .collect();
+    // Above the deterministic range, reject the many composites carrying a
+    // tiny factor before paying for a full modular exponentiation.
+    if trial_division(n).is_some() {
+        return false;
+    }
+
+    let (ref d, ref r) = decompose(n);
+    let mut seeder = Seed(seed);
+    let mut rand = ThreadRandState::new_custom(&mut seeder);
+    let samples: Vec<Integer> = repeat_with(|| n_minus_one.clone().random_below(&mut rand))
+        .filter(|m| m < &n_minus_one)
+        .take(k)
+        .collect();
 
     #[cfg(feature = "rayon")]
     return samples
@@ -154,6 +342,117 @@ pub fn is_prime(n: &Integer, k: usize) -> bool {
         .is_none()
 }
 
+/// The primes below 1000, used by [trial_division] as a cheap pre-sieve.
+const SMALL_PRIMES: [u64; 168] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311, 313, 317, 331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421,
+    431, 433, 439, 443, 449, 457, 461, 463, 467, 479, 487, 491, 499, 503, 509, 521, 523, 541, 547,
+    557, 563, 569, 571, 577, 587, 593, 599, 601, 607, 613, 617, 619, 631, 641, 643, 647, 653, 659,
+    661, 673, 677, 683, 691, 701, 709, 719, 727, 733, 739, 743, 751, 757, 761, 769, 773, 787, 797,
+    809, 811, 821, 823, 827, 829, 839, 853, 857, 859, 863, 877, 881, 883, 887, 907, 911, 919, 929,
+    937, 941, 947, 953, 967, 971, 977, 983, 991, 997,
+];
+
+/// Extract a small prime factor of `n` by trial division against the primes
+/// below 1000.
+///
+/// Returns `Some(p)` for the first tabled prime `p` that divides `n` (and is
+/// strictly smaller than `n`), or `None` when none does. This is a cheap
+/// pre-pass that rejects the many large composites carrying a tiny factor
+/// before a single expensive `pow_mod` is ever computed.
+pub fn trial_division(n: &Integer) -> Option<u64> {
+    SMALL_PRIMES
+        .iter()
+        .copied()
+        .find(|&p| n > &Integer::from(p) && Integer::from(n % p) == 0)
+}
+
+/// Quick rejection: `true` when `cand` is not divisible by any tabled small
+/// prime (other than possibly being that prime itself), and therefore still
+/// worth a full primality test.
+///
+/// Reuses [SMALL_PRIMES], skipping 2 since generated candidates are always
+/// forced odd.
+fn survives_trial_division(cand: &Integer) -> bool {
+    SMALL_PRIMES
+        .iter()
+        .skip(1)
+        .all(|&p| *cand == p || Integer::from(cand % p) != 0)
+}
+
+/// Generate a random probable prime of exactly `bits` bits.
+///
+/// A random odd candidate with the top bit forced set is drawn, cheaply
+/// screened by trial division against a table of small primes, then confirmed
+/// with [is_prime] using `k` iterations; on failure the candidate is advanced
+/// by two until a prime is found (redrawing if the width would overflow).
+///
+/// # Example
+/// ```
+/// use miller_rabin::{generate_prime, is_prime};
+///
+/// let p = generate_prime(64, 16);
+/// assert!(is_prime(&p, 16));
+/// ```
+pub fn generate_prime(bits: u32, k: usize) -> Integer {
+    let mut seed = Seed(rand::random());
+    generate_prime_with(bits, k, false, &mut seed)
+}
+
+/// Like [generate_prime], but additionally requires the result to be a
+/// *safe* prime, i.e. `(p - 1) / 2` is prime as well.
+///
+/// # Example
+/// ```
+/// use miller_rabin::{generate_safe_prime, is_prime};
+///
+/// let p = generate_safe_prime(64, 16);
+/// assert!(is_prime(&p, 16));
+/// let half = rug::Integer::from(&p - 1u8) / 2;
+/// assert!(is_prime(&half, 16));
+/// ```
+pub fn generate_safe_prime(bits: u32, k: usize) -> Integer {
+    let mut seed = Seed(rand::random());
+    generate_prime_with(bits, k, true, &mut seed)
+}
+
+/// Like [generate_prime], but drawing candidates from a caller-supplied
+/// randomness source `gen`.
+pub fn generate_prime_with<R: ThreadRandGen>(
+    bits: u32,
+    k: usize,
+    safe: bool,
+    gen: &mut R,
+) -> Integer {
+    assert!(bits >= 2, "a prime needs at least two bits");
+    let mut rand = ThreadRandState::new_custom(gen);
+
+    loop {
+        let mut cand = Integer::from(Integer::random_bits(bits, &mut rand));
+        cand.set_bit(bits - 1, true); // force the requested width
+        cand.set_bit(0, true); // force the candidate odd
+
+        loop {
+            if survives_trial_division(&cand) && is_prime(&cand, k) {
+                if !safe {
+                    return cand;
+                }
+                let half = Integer::from(&cand - 1u8) / 2;
+                if is_prime(&half, k) {
+                    return cand;
+                }
+            }
+
+            cand += 2u8;
+            if cand.significant_bits() > bits {
+                break; // width overflowed, draw a fresh candidate
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     const K: usize = 16;
@@ -185,6 +484,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_prime_near_u64_max() -> io::Result<()> {
+        // The largest prime below 2^64; regression test for the Montgomery
+        // `mrmul` carry overflow that misclassified moduli this close to
+        // `u64::MAX`.
+        let prime = Integer::from(0xffff_ffff_ffff_ffc5u64); // 2^64 - 59
+        assert!(is_prime(&prime, K));
+        Ok(())
+    }
+
     #[test]
     fn test_small_primes() -> io::Result<()> {
         for prime in &[2u8, 3u8, 5u8, 7u8, 11u8, 13u8] {
@@ -238,6 +547,46 @@ mod tests {
         "5F6E6F2A02DC50C6B63B0AFE7FCBED8E14E696534F8DD8A19734870F4A3C1B7E0EF48B06AE156F729769227BCCBF6670CFFCBCE80661E671BC26D36324AE86C399BD9255D87EC2463CF5DE794C1A49CB7D72018D1DBF615F989E5779B558C8E569B6A577EFFD43FB96D56597542A7FFE663374CE144B488F5D499A0E0036E9D526E835A195969FE6BCDAFBE30EF68C0DB9A596E0E434F24C59323F462180EDFE8BC3F8E3FAF343E88C7952EA086DB9B44AC31BBD54939EF76028DB06DC09EE86117D6AB0DD5F1E2CE633F59421C3F7369FC61C7B5059A6F41677C94DC29E1D8D296366B5C3D5054416187C5B8B59B43E65C75CF60DFB3A03E28A118AE95EFFD2E9BF056DCB42C9DE3354CCB4AEF88D80B2590D317BD0538036A4F7C6F598A0473356A9D2535F1C7907784E426394D4AA276FC2A13A6E1090657D0DE0471073E3F8CB4EE6A616046E5C55A0CDB5459178EB78C1D8C8972A5822E4274AF3346941039F7C90B7188360B9FFCD0E94EE22282CA48904FD4AA06835B33308F5AF673B"
     ];
 
+    #[test]
+    fn test_trial_division() -> io::Result<()> {
+        // A large prime has no small factor.
+        let prime = Integer::from_str_radix(BIG_PRIMES[0], 16).unwrap();
+        assert_eq!(trial_division(&prime), None);
+        // 3 times a large prime is caught by the pre-sieve.
+        let composite = Integer::from(3) * &prime;
+        assert_eq!(trial_division(&composite), Some(3));
+        assert!(!is_prime(&composite, K));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_prime_seeded_reproducible() -> io::Result<()> {
+        let composite = Integer::from_str_radix(BIG_COMPOSITE[0], 16).unwrap();
+        assert_eq!(
+            is_prime_seeded(&composite, K, 0xDEAD_BEEF),
+            is_prime_seeded(&composite, K, 0xDEAD_BEEF),
+        );
+        assert!(!is_prime_seeded(&composite, K, 0xDEAD_BEEF));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_prime() -> io::Result<()> {
+        let p = generate_prime(128, K);
+        assert_eq!(p.significant_bits(), 128);
+        assert!(is_prime(&p, K));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_safe_prime() -> io::Result<()> {
+        let p = generate_safe_prime(64, K);
+        assert!(is_prime(&p, K));
+        let half = Integer::from(&p - 1u8) / 2;
+        assert!(is_prime(&half, K));
+        Ok(())
+    }
+
     #[test]
     fn test_3072_prime() {
         for p_str in BIG_PRIMES {